@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use ndarray::{Array4, ArrayD};
+use ort::session::{Session, builder::GraphOptimizationLevel};
+use ort::value::Tensor;
+
+use crate::segmenter::Inferer;
+
+/// Which inference runtime executes a model's tensors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// ONNX Runtime via the `ort` crate (the default). Supports the
+    /// CUDA/TensorRT/DirectML execution providers behind their respective
+    /// feature flags.
+    Ort,
+    /// A pure-CPU runtime via the `tract` crate (feature `tract`). Slower
+    /// than `ort`, but needs no onnxruntime shared library.
+    #[cfg(feature = "tract")]
+    Tract,
+}
+
+/// Build and warm up an `ort::Session`, honoring the requested intra-op
+/// thread count and (feature-gated) execution provider. Shared by every
+/// model that runs on the `ort` backend.
+#[allow(clippy::too_many_arguments)]
+pub fn build_ort_session(
+    model_path: &str,
+    threads: usize,
+    use_cuda: bool,
+    use_tensorrt: bool,
+    use_directml: bool,
+    device_id: i32,
+) -> Result<Session> {
+    let mut builder = Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_intra_threads(threads)?;
+
+    // Optional execution providers (feature-gated at compile time)
+    #[cfg(feature = "tensorrt")]
+    if use_tensorrt {
+        use ort::execution_providers::TensorRTExecutionProvider;
+        let trt = TensorRTExecutionProvider::default()
+            .with_device_id(device_id)
+            .build()
+            .error_on_failure();
+        builder = builder.with_execution_providers([trt])?;
+    }
+
+    #[cfg(feature = "cuda")]
+    if use_cuda {
+        use ort::execution_providers::CUDAExecutionProvider;
+        let cuda = CUDAExecutionProvider::default()
+            .with_device_id(device_id)
+            .build()
+            .error_on_failure();
+        builder = builder.with_execution_providers([cuda])?;
+    }
+
+    #[cfg(feature = "directml")]
+    if use_directml {
+        use ort::execution_providers::DirectMLExecutionProvider;
+        let dml = DirectMLExecutionProvider::default()
+            .with_device_id(device_id)
+            .build()
+            .error_on_failure();
+        builder = builder.with_execution_providers([dml])?;
+    }
+
+    #[cfg(not(any(feature = "cuda", feature = "tensorrt", feature = "directml")))]
+    {
+        if use_cuda || use_tensorrt || use_directml {
+            eprintln!(
+                "Note: you passed --use-cuda/--use-tensorrt/--use-directml but the binary was not built with those features."
+            );
+        }
+    }
+
+    builder
+        .commit_from_file(model_path)
+        .with_context(|| format!("Failed to load ONNX model: {}", model_path))
+}
+
+/// Runs a model's tensors through `ort::Session::run`.
+pub struct OrtInferer<'a> {
+    pub session: &'a mut Session,
+}
+
+impl Inferer for OrtInferer<'_> {
+    fn infer(&mut self, input: Array4<f32>) -> Result<ArrayD<f32>> {
+        let input_tensor = Tensor::from_array(input)?;
+        // positional input (works regardless of input name)
+        let outputs = self.session.run(ort::inputs![input_tensor])?;
+        let arr_view = outputs[0].try_extract_array::<f32>()?;
+        Ok(arr_view.to_owned().into_dyn())
+    }
+}
+
+#[cfg(feature = "tract")]
+mod tract_backend {
+    use super::*;
+    use tract_onnx::prelude::*;
+
+    /// A loaded `tract` CPU plan, wrapped so it can implement [`Inferer`].
+    pub struct TractInferer {
+        plan: TypedRunnableModel<TypedModel>,
+    }
+
+    impl TractInferer {
+        pub fn build(model_path: &str) -> Result<Self> {
+            let plan = tract_onnx::onnx()
+                .model_for_path(model_path)
+                .with_context(|| format!("Failed to load ONNX model for tract: {}", model_path))?
+                .into_optimized()
+                .with_context(|| format!("Failed to optimize ONNX model for tract: {}", model_path))?
+                .into_runnable()
+                .with_context(|| format!("Failed to plan ONNX model for tract: {}", model_path))?;
+            Ok(Self { plan })
+        }
+    }
+
+    impl Inferer for TractInferer {
+        fn infer(&mut self, input: Array4<f32>) -> Result<ArrayD<f32>> {
+            let input: Tensor = input.into_dyn().into();
+            let outputs = self.plan.run(tvec!(input.into()))?;
+            let arr = outputs[0].to_array_view::<f32>()?.to_owned();
+            Ok(arr)
+        }
+    }
+}
+
+#[cfg(feature = "tract")]
+pub use tract_backend::TractInferer;
+
+/// Owns whichever concrete runtime is backing a model, built once per run
+/// and reused for every image (batch mode) or the single input (one-shot
+/// mode). Implements [`Inferer`] directly so callers don't need to match on
+/// the backend themselves.
+pub enum ModelBackend {
+    Ort(Session),
+    #[cfg(feature = "tract")]
+    Tract(TractInferer),
+}
+
+impl ModelBackend {
+    #[allow(clippy::too_many_arguments)]
+    pub fn build(
+        backend: Backend,
+        model_path: &str,
+        threads: usize,
+        use_cuda: bool,
+        use_tensorrt: bool,
+        use_directml: bool,
+        device_id: i32,
+    ) -> Result<Self> {
+        match backend {
+            Backend::Ort => Ok(ModelBackend::Ort(build_ort_session(
+                model_path,
+                threads,
+                use_cuda,
+                use_tensorrt,
+                use_directml,
+                device_id,
+            )?)),
+            #[cfg(feature = "tract")]
+            Backend::Tract => Ok(ModelBackend::Tract(TractInferer::build(model_path)?)),
+        }
+    }
+
+    /// The `ort::Session` backing this backend, if it's the `ort` one.
+    /// [`crate::preprocess::detect_layout`] and
+    /// [`crate::preprocess::resolve_target_size`] need a live `Session` to
+    /// introspect the ONNX graph's declared input shape; on the `tract`
+    /// backend callers fall back to the caller-supplied/default size and the
+    /// crate's original NCHW layout assumption instead.
+    pub fn session(&mut self) -> Option<&mut Session> {
+        match self {
+            ModelBackend::Ort(session) => Some(session),
+            #[cfg(feature = "tract")]
+            ModelBackend::Tract(_) => None,
+        }
+    }
+}
+
+impl Inferer for ModelBackend {
+    fn infer(&mut self, input: Array4<f32>) -> Result<ArrayD<f32>> {
+        match self {
+            ModelBackend::Ort(session) => OrtInferer { session }.infer(input),
+            #[cfg(feature = "tract")]
+            ModelBackend::Tract(inferer) => inferer.infer(input),
+        }
+    }
+}
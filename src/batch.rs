@@ -0,0 +1,340 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use anyhow::{Context, Result};
+use image::{ImageReader, RgbImage};
+
+use crate::backend::{Backend, ModelBackend};
+use crate::modnet::resolve_modnet_segmenter;
+use crate::postprocess::{OutputOptions, write_output};
+use crate::preprocess::{Layout, PaddingMode};
+use crate::segmenter::{Segmenter, segment};
+use crate::u2net::resolve_u2net_segmenter;
+
+/// Which model a batch run should drive.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ModelKind {
+    Modnet,
+    U2net,
+}
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "webp", "tif", "tiff"];
+
+fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| IMAGE_EXTENSIONS.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+        .unwrap_or(false)
+}
+
+fn collect_input_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read input directory: {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_image_file(path))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn collect_glob_files(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = glob::glob(pattern)
+        .with_context(|| format!("Invalid glob pattern: {pattern}"))?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file() && is_image_file(path))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// A decoded image still tagged with the path it came from, so a result can
+/// be written to the right output file regardless of which worker finished
+/// decoding it first.
+struct DecodedImage {
+    path: PathBuf,
+    image: RgbImage,
+}
+
+/// Assign each input its `.png` output path up front, from `files`'s stable
+/// sorted order rather than the order images finish decoding in. Different
+/// inputs with the same stem (e.g. `cat.png` and `cat.jpg`) would otherwise
+/// collide on the shared output extension and silently overwrite each
+/// other; deciding collisions here instead of in the decode-completion loop
+/// keeps the `_2`/`_3` suffixes reproducible across runs of the same batch.
+fn assign_output_paths(files: &[PathBuf], output_dir: &Path) -> HashMap<PathBuf, PathBuf> {
+    let mut stem_counts: HashMap<String, usize> = HashMap::new();
+    let mut out_paths = HashMap::with_capacity(files.len());
+
+    for path in files {
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("output")
+            .to_string();
+        let count = stem_counts.entry(stem.clone()).or_insert(0);
+        *count += 1;
+        let out_path = if *count == 1 {
+            output_dir.join(&stem).with_extension("png")
+        } else {
+            let disambiguated = format!("{stem}_{count}");
+            eprintln!(
+                "Warning: {} shares output name {stem:?} with an earlier input; writing {disambiguated}.png instead of overwriting it",
+                path.display()
+            );
+            output_dir.join(disambiguated).with_extension("png")
+        };
+        out_paths.insert(path.clone(), out_path);
+    }
+
+    out_paths
+}
+
+/// Process every image in `input_dir` through a single shared model backend,
+/// writing one output per input (per `output_opts`) into `output_dir`.
+///
+/// Decoding runs on a pool of worker threads capped at
+/// `std::thread::available_parallelism`, so file I/O and JPEG/PNG decode for
+/// the next images overlap with inference on the current one
+/// instead of serializing decode-then-infer per image. The session and its
+/// execution provider warm-up are paid for once across the whole batch.
+#[allow(clippy::too_many_arguments)]
+pub fn run_batch(
+    model: ModelKind,
+    model_path: &str,
+    input_dir: &str,
+    output_dir: &str,
+    threads: usize,
+    use_cuda: bool,
+    use_tensorrt: bool,
+    use_directml: bool,
+    device_id: i32,
+    input_size: Option<u32>,
+    layout: Option<Layout>,
+    padding_mode: PaddingMode,
+    backend: Backend,
+    output_opts: &OutputOptions,
+) -> Result<()> {
+    let input_dir = Path::new(input_dir);
+    let files = collect_input_files(input_dir)?;
+    if files.is_empty() {
+        println!("No input images found in {}", input_dir.display());
+        return Ok(());
+    }
+
+    run_batch_files(
+        model,
+        model_path,
+        files,
+        output_dir,
+        threads,
+        use_cuda,
+        use_tensorrt,
+        use_directml,
+        device_id,
+        input_size,
+        layout,
+        padding_mode,
+        backend,
+        output_opts,
+    )
+}
+
+/// Same as [`run_batch`], but `input_glob` is a glob pattern (e.g.
+/// `imgs/*.jpg`) matched against the filesystem instead of a directory
+/// listing, so callers can point at a subset of files spread across
+/// directories.
+#[allow(clippy::too_many_arguments)]
+pub fn run_batch_glob(
+    model: ModelKind,
+    model_path: &str,
+    input_glob: &str,
+    output_dir: &str,
+    threads: usize,
+    use_cuda: bool,
+    use_tensorrt: bool,
+    use_directml: bool,
+    device_id: i32,
+    input_size: Option<u32>,
+    layout: Option<Layout>,
+    padding_mode: PaddingMode,
+    backend: Backend,
+    output_opts: &OutputOptions,
+) -> Result<()> {
+    let files = collect_glob_files(input_glob)?;
+    if files.is_empty() {
+        println!("No input images matched {input_glob}");
+        return Ok(());
+    }
+
+    run_batch_files(
+        model,
+        model_path,
+        files,
+        output_dir,
+        threads,
+        use_cuda,
+        use_tensorrt,
+        use_directml,
+        device_id,
+        input_size,
+        layout,
+        padding_mode,
+        backend,
+        output_opts,
+    )
+}
+
+/// Shared worker-pool pipeline behind [`run_batch`] and [`run_batch_glob`]:
+/// both just differ in how `files` gets collected.
+#[allow(clippy::too_many_arguments)]
+fn run_batch_files(
+    model: ModelKind,
+    model_path: &str,
+    files: Vec<PathBuf>,
+    output_dir: &str,
+    threads: usize,
+    use_cuda: bool,
+    use_tensorrt: bool,
+    use_directml: bool,
+    device_id: i32,
+    input_size: Option<u32>,
+    layout: Option<Layout>,
+    padding_mode: PaddingMode,
+    backend: Backend,
+    output_opts: &OutputOptions,
+) -> Result<()> {
+    let output_dir = Path::new(output_dir);
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create output directory: {}", output_dir.display()))?;
+
+    let worker_count = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(files.len());
+
+    let mut model_backend = ModelBackend::build(
+        backend,
+        model_path,
+        threads,
+        use_cuda,
+        use_tensorrt,
+        use_directml,
+        device_id,
+    )?;
+    let segmenter: Box<dyn Segmenter> = match model {
+        ModelKind::Modnet => Box::new(resolve_modnet_segmenter(&mut model_backend, layout, input_size)),
+        ModelKind::U2net => Box::new(resolve_u2net_segmenter(&mut model_backend, layout, input_size)),
+    };
+
+    let output_paths = assign_output_paths(&files, output_dir);
+
+    let (path_tx, path_rx) = mpsc::channel::<PathBuf>();
+    let path_rx = Mutex::new(path_rx);
+    // Bounded so decode can't run arbitrarily far ahead of the single
+    // inference loop below: decode is usually faster than inference, and an
+    // unbounded channel would let a worker pool decode a whole huge folder
+    // into memory before any of it gets consumed.
+    let (decoded_tx, decoded_rx) = mpsc::sync_channel::<Result<DecodedImage>>(worker_count);
+
+    for path in files {
+        path_tx.send(path)?;
+    }
+    drop(path_tx);
+
+    thread::scope(|scope| {
+        for _ in 0..worker_count {
+            let path_rx = &path_rx;
+            let decoded_tx = decoded_tx.clone();
+            scope.spawn(move || {
+                while let Ok(path) = path_rx.lock().unwrap().recv() {
+                    let decoded = ImageReader::open(&path)
+                        .with_context(|| format!("Failed to open {}", path.display()))
+                        .and_then(|reader| {
+                            reader
+                                .decode()
+                                .with_context(|| format!("Failed to decode {}", path.display()))
+                        })
+                        .map(|img| DecodedImage {
+                            path: path.clone(),
+                            image: img.to_rgb8(),
+                        });
+                    // The receiver only disappears once the inference loop
+                    // below stops early, on an error or a panic, so a send
+                    // error here just means nobody is left to take the image.
+                    let _ = decoded_tx.send(decoded);
+                }
+            });
+        }
+        drop(decoded_tx);
+
+        let mut processed = 0usize;
+        for decoded in decoded_rx {
+            let DecodedImage { path, image } = match decoded {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    eprintln!("Skipping {err:#}");
+                    continue;
+                }
+            };
+
+            let matte = segment(segmenter.as_ref(), &mut model_backend, &image, padding_mode)?;
+
+            let out_path = &output_paths[&path];
+            write_output(&image, matte, out_path, output_opts)?;
+            println!("Saved {}", out_path.display());
+            processed += 1;
+        }
+
+        println!("Processed {processed} image(s) into {}", output_dir.display());
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assign_output_paths_gives_each_unique_stem_its_own_file() {
+        let output_dir = Path::new("/out");
+        let files = vec![PathBuf::from("/in/cat.png"), PathBuf::from("/in/dog.png")];
+        let out_paths = assign_output_paths(&files, output_dir);
+
+        assert_eq!(out_paths[&files[0]], output_dir.join("cat.png"));
+        assert_eq!(out_paths[&files[1]], output_dir.join("dog.png"));
+    }
+
+    #[test]
+    fn assign_output_paths_disambiguates_a_stem_collision() {
+        let output_dir = Path::new("/out");
+        let files = vec![
+            PathBuf::from("/in/cat.png"),
+            PathBuf::from("/in/cat.jpg"),
+            PathBuf::from("/in/other/cat.bmp"),
+        ];
+        let out_paths = assign_output_paths(&files, output_dir);
+
+        assert_eq!(out_paths[&files[0]], output_dir.join("cat.png"));
+        assert_eq!(out_paths[&files[1]], output_dir.join("cat_2.png"));
+        assert_eq!(out_paths[&files[2]], output_dir.join("cat_3.png"));
+    }
+
+    #[test]
+    fn assign_output_paths_is_order_independent_of_decode_completion() {
+        // The map is built up front from `files`'s order, not the order a
+        // caller looks entries up in (the order decoding finishes), so
+        // looking up the later file first still gives it the `_2` suffix.
+        let output_dir = Path::new("/out");
+        let files = vec![PathBuf::from("/a/img.png"), PathBuf::from("/b/img.png")];
+        let out_paths = assign_output_paths(&files, output_dir);
+
+        assert_eq!(out_paths[&files[1]], output_dir.join("img_2.png"));
+        assert_eq!(out_paths[&files[0]], output_dir.join("img.png"));
+    }
+}
@@ -1,7 +1,18 @@
+pub mod backend;
+pub mod batch;
 pub mod modnet;
+pub mod postprocess;
 pub mod preprocess;
+pub mod segmenter;
+pub mod serve;
+mod simd;
 pub mod u2net;
 
+pub use backend::*;
+pub use batch::*;
 pub use modnet::*;
+pub use postprocess::*;
 pub use preprocess::*;
+pub use segmenter::*;
+pub use serve::*;
 pub use u2net::*;
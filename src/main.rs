@@ -1,23 +1,34 @@
 use anyhow::{Context, Result, anyhow};
-use clap::{Parser, ValueEnum};
-use segment_foreground_rs::{run_modnet, run_u2net};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use segment_foreground_rs::{
+    Backend, EmitMode, Layout, ModelKind, OutputOptions, PaddingMode, run_batch, run_batch_glob,
+    run_modnet, run_serve, run_u2net,
+};
 use std::{env, path::PathBuf};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Segment a single image, every image in a directory, or every image matching a glob
+    Run(RunArgs),
+    /// Keep the model warm and segment images submitted over HTTP
+    Serve(ServeArgs),
+}
+
+/// Options shared by every mode: which model to run, how to run it, and
+/// what to write out.
+#[derive(Args)]
+struct ModelArgs {
     /// Model to use for segmentation
     #[arg(value_enum, long, short, default_value_t = Model::Modnet)]
     model: Model,
 
-    /// Path to the input file
-    #[arg(short, long, value_parser = validate_file_exists)]
-    input: PathBuf,
-
-    /// Path to the output file
-    #[arg(short, long, default_value = "matte.png")]
-    output: PathBuf,
-
     /// Number of intra-op threads for ORT
     #[arg(long, default_value_t = 4)]
     pub threads: usize,
@@ -38,6 +49,168 @@ struct Cli {
     /// Device (GPU) ID to be used with CUDA, TensorRT, or DirectML
     #[arg(short, long, default_value_t = 0)]
     pub device_id: i32,
+
+    /// Override the model's square input resolution when the ONNX graph
+    /// declares a dynamic input shape (ignored for models with a fixed shape)
+    #[arg(long)]
+    pub input_size: Option<u32>,
+
+    /// Tensor layout the model expects; auto-detected from the input shape by default
+    #[arg(value_enum, long, default_value_t = LayoutArg::Auto)]
+    pub layout: LayoutArg,
+
+    /// How to fill the letterbox margins added by aspect-preserving resize
+    #[arg(value_enum, long, default_value_t = PaddingArg::Constant)]
+    pub padding: PaddingArg,
+
+    /// Inference runtime to run the model on
+    #[arg(value_enum, long, default_value_t = BackendArg::Ort)]
+    pub backend: BackendArg,
+
+    /// What to write out: the grayscale matte, an RGBA composite, or a
+    /// cutout cropped to the foreground's bounding box
+    #[arg(value_enum, long, default_value_t = EmitArg::Matte)]
+    pub emit: EmitArg,
+
+    /// Alpha cutoff (0-255) used to find the foreground bounding box with `--emit cutout`
+    #[arg(long, default_value_t = 10)]
+    pub alpha_threshold: u8,
+
+    /// Premultiply RGB by alpha in `--emit rgba`/`cutout` output
+    #[arg(long, default_value_t = false)]
+    pub premultiplied: bool,
+
+    /// Extra border, in pixels, added around the bounding box with `--emit cutout`
+    #[arg(long, default_value_t = 0)]
+    pub cutout_margin: u32,
+
+    /// Letterbox the cutout onto a fixed square canvas of this size with `--emit cutout`
+    #[arg(long)]
+    pub cutout_size: Option<u32>,
+}
+
+impl ModelArgs {
+    fn output_opts(&self) -> OutputOptions {
+        OutputOptions {
+            emit: self.emit.into(),
+            alpha_threshold: self.alpha_threshold,
+            premultiplied: self.premultiplied,
+            cutout_margin: self.cutout_margin,
+            cutout_size: self.cutout_size.map(|size| (size, size)),
+        }
+    }
+}
+
+#[derive(Args)]
+struct RunArgs {
+    #[command(flatten)]
+    model_args: ModelArgs,
+
+    /// Path to the input file, a directory to batch-process every image in it,
+    /// or a glob pattern (e.g. `imgs/*.jpg`) to batch-process every match
+    #[arg(short, long, value_parser = validate_input_exists)]
+    input: PathBuf,
+
+    /// Path to the output file (or output directory, in batch mode)
+    #[arg(short, long, default_value = "matte.png")]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    #[command(flatten)]
+    model_args: ModelArgs,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum EmitArg {
+    /// Grayscale alpha matte only (original behavior)
+    Matte,
+    /// Original RGB composited with the alpha into an RGBA PNG
+    Rgba,
+    /// RGBA composite, tight-cropped to the foreground's bounding box
+    Cutout,
+}
+
+impl From<EmitArg> for EmitMode {
+    fn from(arg: EmitArg) -> Self {
+        match arg {
+            EmitArg::Matte => EmitMode::Matte,
+            EmitArg::Rgba => EmitMode::Rgba,
+            EmitArg::Cutout => EmitMode::Cutout,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum PaddingArg {
+    /// Solid black margins
+    Constant,
+    /// Clamp to the nearest boundary pixel
+    Edge,
+    /// Mirror the resized content into the margins
+    Reflect,
+}
+
+impl From<PaddingArg> for PaddingMode {
+    fn from(arg: PaddingArg) -> Self {
+        match arg {
+            PaddingArg::Constant => PaddingMode::Constant,
+            PaddingArg::Edge => PaddingMode::Edge,
+            PaddingArg::Reflect => PaddingMode::Reflect,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum BackendArg {
+    /// ONNX Runtime (default); supports CUDA/TensorRT/DirectML if built with
+    /// the matching feature
+    Ort,
+    /// Pure-CPU `tract` runtime (requires building with --features tract);
+    /// no onnxruntime shared library needed
+    Tract,
+}
+
+impl From<BackendArg> for Backend {
+    fn from(arg: BackendArg) -> Self {
+        match arg {
+            BackendArg::Ort => Backend::Ort,
+            #[cfg(feature = "tract")]
+            BackendArg::Tract => Backend::Tract,
+            #[cfg(not(feature = "tract"))]
+            BackendArg::Tract => {
+                eprintln!(
+                    "Note: --backend tract was requested but the binary was not built with the `tract` feature; falling back to ort."
+                );
+                Backend::Ort
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum LayoutArg {
+    /// Detect NCHW vs NHWC from the model's declared input shape
+    Auto,
+    /// Force channel-first `(1, 3, H, W)`
+    Nchw,
+    /// Force channel-last `(1, H, W, 3)`
+    Nhwc,
+}
+
+impl LayoutArg {
+    fn resolve(self) -> Option<Layout> {
+        match self {
+            LayoutArg::Auto => None,
+            LayoutArg::Nchw => Some(Layout::Nchw),
+            LayoutArg::Nhwc => Some(Layout::Nhwc),
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -48,34 +221,115 @@ enum Model {
     U2net,
 }
 
+fn model_kind_and_file(model: Model) -> (ModelKind, &'static str) {
+    match model {
+        Model::Modnet => (ModelKind::Modnet, "models/modnet.onnx"),
+        Model::U2net => (ModelKind::U2net, "models/u2net.onnx"),
+    }
+}
+
 fn main() -> Result<()> {
-    let args = Cli::parse();
+    match Cli::parse_from(normalize_args(env::args())).command {
+        Command::Run(args) => run(args),
+        Command::Serve(args) => serve(args),
+    }
+}
 
-    match &args.model {
+/// `run`/`serve` predate the subcommand split: every existing invocation
+/// passed `run`'s flags directly (`segment-foreground-rs --model modnet
+/// --input x --output y`). Insert `run` when the first argument isn't
+/// already a recognized subcommand or a top-level `--help`/`--version`, so
+/// those invocations keep working unchanged.
+fn normalize_args(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut args: Vec<String> = args.collect();
+    let needs_run = matches!(
+        args.get(1).map(String::as_str),
+        Some(arg) if !matches!(arg, "run" | "serve" | "-h" | "--help" | "-V" | "--version")
+    );
+    if needs_run {
+        args.insert(1, "run".to_string());
+    }
+    args
+}
+
+fn run(args: RunArgs) -> Result<()> {
+    let (model_kind, model_file) = model_kind_and_file(args.model_args.model);
+    let model_path = get_model_path_with_fallback(model_file)?;
+
+    let input_str = args.input.to_str().unwrap();
+    if looks_like_glob(input_str) {
+        run_batch_glob(
+            model_kind,
+            model_path.to_str().unwrap(),
+            input_str,
+            args.output.to_str().unwrap(),
+            args.model_args.threads,
+            args.model_args.use_cuda,
+            args.model_args.use_tensorrt,
+            args.model_args.use_directml,
+            args.model_args.device_id,
+            args.model_args.input_size,
+            args.model_args.layout.resolve(),
+            args.model_args.padding.into(),
+            args.model_args.backend.into(),
+            &args.model_args.output_opts(),
+        )?;
+        return Ok(());
+    }
+
+    if args.input.is_dir() {
+        run_batch(
+            model_kind,
+            model_path.to_str().unwrap(),
+            input_str,
+            args.output.to_str().unwrap(),
+            args.model_args.threads,
+            args.model_args.use_cuda,
+            args.model_args.use_tensorrt,
+            args.model_args.use_directml,
+            args.model_args.device_id,
+            args.model_args.input_size,
+            args.model_args.layout.resolve(),
+            args.model_args.padding.into(),
+            args.model_args.backend.into(),
+            &args.model_args.output_opts(),
+        )?;
+        return Ok(());
+    }
+
+    match args.model_args.model {
         Model::Modnet => {
-            let model_path = get_model_path_with_fallback("models/modnet.onnx")?;
             run_modnet(
                 model_path.to_str().unwrap(),
                 args.input.to_str().unwrap(),
                 args.output.to_str().unwrap(),
-                args.threads,
-                args.use_cuda,
-                args.use_tensorrt,
-                args.use_directml,
-                args.device_id,
+                args.model_args.threads,
+                args.model_args.use_cuda,
+                args.model_args.use_tensorrt,
+                args.model_args.use_directml,
+                args.model_args.device_id,
+                args.model_args.input_size,
+                args.model_args.layout.resolve(),
+                args.model_args.padding.into(),
+                args.model_args.backend.into(),
+                &args.model_args.output_opts(),
             )?;
         }
         Model::U2net => {
-            let model_path = get_model_path_with_fallback("models/u2net.onnx")?;
             run_u2net(
                 model_path.to_str().unwrap(),
                 args.input.to_str().unwrap(),
                 args.output.to_str().unwrap(),
-                args.threads,
-                args.use_cuda,
-                args.use_tensorrt,
-                args.use_directml,
-                args.device_id,
+                args.model_args.threads,
+                args.model_args.use_cuda,
+                args.model_args.use_tensorrt,
+                args.model_args.use_directml,
+                args.model_args.device_id,
+                args.model_args.input_size,
+                args.model_args.layout.resolve(),
+                args.model_args.padding.into(),
+                args.model_args.backend.into(),
+                &args.model_args.output_opts(),
             )?;
         }
     }
@@ -83,6 +337,46 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+fn serve(args: ServeArgs) -> Result<()> {
+    let (model_kind, model_file) = model_kind_and_file(args.model_args.model);
+    let model_path = get_model_path_with_fallback(model_file)?;
+
+    run_serve(
+        model_kind,
+        model_path.to_str().unwrap(),
+        &args.addr,
+        args.model_args.threads,
+        args.model_args.use_cuda,
+        args.model_args.use_tensorrt,
+        args.model_args.use_directml,
+        args.model_args.device_id,
+        args.model_args.input_size,
+        args.model_args.layout.resolve(),
+        args.model_args.padding.into(),
+        args.model_args.backend.into(),
+        args.model_args.output_opts(),
+    )
+}
+
+fn validate_input_exists(path: &str) -> Result<PathBuf, String> {
+    if looks_like_glob(path) {
+        return Ok(PathBuf::from(path));
+    }
+    let path = PathBuf::from(path);
+    if path.exists() {
+        Ok(path)
+    } else {
+        Err(format!("Input path does not exist: {}", path.display()))
+    }
+}
+
+/// Whether `--input` looks like a glob pattern rather than a literal path, so
+/// it can be dispatched to [`run_batch_glob`] instead of failing existence
+/// validation (a pattern like `imgs/*.jpg` is never itself an existing path).
+fn looks_like_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
 fn validate_file_exists(path: &str) -> Result<PathBuf, String> {
     let path = PathBuf::from(path);
     if path.exists() && path.is_file() {
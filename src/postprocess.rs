@@ -0,0 +1,251 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use anyhow::Result;
+use image::{DynamicImage, GrayImage, ImageFormat, Rgba, RgbaImage, RgbImage, imageops::FilterType};
+
+/// What kind of artifact the CLI should write out.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EmitMode {
+    /// Grayscale alpha matte only (original behavior).
+    Matte,
+    /// The original RGB image composited with the alpha into an RGBA PNG.
+    Rgba,
+    /// An RGBA composite, tight-cropped to the foreground's bounding box.
+    Cutout,
+}
+
+/// Options controlling what [`write_output`] produces from a matte.
+#[derive(Copy, Clone, Debug)]
+pub struct OutputOptions {
+    pub emit: EmitMode,
+    /// Alpha cutoff (0-255) used to find the foreground bounding box in `Cutout` mode.
+    pub alpha_threshold: u8,
+    /// Premultiply RGB by alpha in `Rgba`/`Cutout` mode.
+    pub premultiplied: bool,
+    /// Extra border, in pixels, added around the bounding box in `Cutout` mode.
+    pub cutout_margin: u32,
+    /// Fixed `(width, height)` canvas to letterbox the cutout into; `None` keeps the tight crop.
+    pub cutout_size: Option<(u32, u32)>,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            emit: EmitMode::Matte,
+            alpha_threshold: 10,
+            premultiplied: false,
+            cutout_margin: 0,
+            cutout_size: None,
+        }
+    }
+}
+
+/// Render the final artifact for one image per `opts.emit`: the raw
+/// grayscale matte, an RGBA composite, or an RGBA composite tight-cropped
+/// (and optionally letterboxed onto a fixed canvas) to the foreground.
+/// Shared by [`encode_output`] and [`write_output`], which just differ in
+/// how the result gets serialized.
+fn render_output(img: &RgbImage, matte: GrayImage, opts: &OutputOptions) -> DynamicImage {
+    match opts.emit {
+        EmitMode::Matte => DynamicImage::ImageLuma8(matte),
+        EmitMode::Rgba => DynamicImage::ImageRgba8(composite_rgba(img, &matte, opts.premultiplied)),
+        EmitMode::Cutout => {
+            let rgba = composite_rgba(img, &matte, opts.premultiplied);
+            let bbox = alpha_bbox(&matte, opts.alpha_threshold, opts.cutout_margin)
+                .unwrap_or((0, 0, img.width(), img.height()));
+            DynamicImage::ImageRgba8(crop_to_cutout(&rgba, bbox, opts.cutout_size))
+        }
+    }
+}
+
+/// Render the final output for one image per `opts.emit` and PNG-encode it
+/// in memory. Used by `serve`, which returns the bytes directly over HTTP
+/// instead of writing them to disk, so it always wants a fixed format
+/// regardless of any output path.
+pub fn encode_output(img: &RgbImage, matte: GrayImage, opts: &OutputOptions) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut cursor = Cursor::new(&mut buf);
+    render_output(img, matte, opts).write_to(&mut cursor, ImageFormat::Png)?;
+    Ok(buf)
+}
+
+/// Write the final output for one image to `output_path`, per `opts.emit`.
+/// The file format is picked from `output_path`'s extension, same as the
+/// plain `matte.save(output_path)` this replaced.
+pub fn write_output(
+    img: &RgbImage,
+    matte: GrayImage,
+    output_path: &Path,
+    opts: &OutputOptions,
+) -> Result<()> {
+    render_output(img, matte, opts).save(output_path)?;
+    Ok(())
+}
+
+/// Composite an RGB image with its alpha matte into RGBA, optionally
+/// premultiplying the color channels by alpha.
+pub fn composite_rgba(img: &RgbImage, alpha: &GrayImage, premultiplied: bool) -> RgbaImage {
+    let (w, h) = img.dimensions();
+    let mut out = RgbaImage::new(w, h);
+    for y in 0..h {
+        for x in 0..w {
+            let px = img.get_pixel(x, y);
+            let a = alpha.get_pixel(x, y)[0];
+            let (r, g, b) = if premultiplied {
+                let scale = a as f32 / 255.0;
+                (
+                    (px[0] as f32 * scale).round() as u8,
+                    (px[1] as f32 * scale).round() as u8,
+                    (px[2] as f32 * scale).round() as u8,
+                )
+            } else {
+                (px[0], px[1], px[2])
+            };
+            out.put_pixel(x, y, Rgba([r, g, b, a]));
+        }
+    }
+    out
+}
+
+/// Thresholded bounding box of the foreground as `(x, y, w, h)`, expanded by
+/// `margin` pixels and clamped to the image bounds. Returns `None` if no
+/// pixel clears `threshold`.
+pub fn alpha_bbox(alpha: &GrayImage, threshold: u8, margin: u32) -> Option<(u32, u32, u32, u32)> {
+    let (w, h) = alpha.dimensions();
+    let mut min_x = u32::MAX;
+    let mut min_y = u32::MAX;
+    let mut max_x = 0u32;
+    let mut max_y = 0u32;
+    let mut found = false;
+
+    for y in 0..h {
+        for x in 0..w {
+            if alpha.get_pixel(x, y)[0] >= threshold {
+                found = true;
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        }
+    }
+
+    if !found {
+        return None;
+    }
+
+    let x0 = min_x.saturating_sub(margin);
+    let y0 = min_y.saturating_sub(margin);
+    let x1 = max_x.saturating_add(1).saturating_add(margin).min(w);
+    let y1 = max_y.saturating_add(1).saturating_add(margin).min(h);
+    Some((x0, y0, x1 - x0, y1 - y0))
+}
+
+/// Crop `img` to `bbox` and, if `canvas_size` is given, letterbox-fit the
+/// crop into a canvas of that size with a bilinear resize and transparent
+/// margins (the RGBA counterpart to
+/// [`crate::preprocess::resize_with_padding`]).
+pub fn crop_to_cutout(
+    img: &RgbaImage,
+    bbox: (u32, u32, u32, u32),
+    canvas_size: Option<(u32, u32)>,
+) -> RgbaImage {
+    let (x, y, w, h) = bbox;
+    let cropped = image::imageops::crop_imm(img, x, y, w, h).to_image();
+
+    let Some((canvas_w, canvas_h)) = canvas_size else {
+        return cropped;
+    };
+    if canvas_w == 0 || canvas_h == 0 {
+        return RgbaImage::new(canvas_w, canvas_h);
+    }
+
+    let scale = f32::min(canvas_w as f32 / w as f32, canvas_h as f32 / h as f32);
+    // Clamped to `canvas_w`/`canvas_h`, not just floored at 1: the `pad_x`/
+    // `pad_y` subtraction below assumes `new_w <= canvas_w`, which rounding
+    // up from a tiny `scale` could otherwise violate and underflow.
+    let new_w = ((w as f32 * scale).round() as u32).clamp(1, canvas_w);
+    let new_h = ((h as f32 * scale).round() as u32).clamp(1, canvas_h);
+    let resized = image::imageops::resize(&cropped, new_w, new_h, FilterType::Triangle);
+
+    let pad_x = (canvas_w - new_w) / 2;
+    let pad_y = (canvas_h - new_h) / 2;
+    let mut canvas = RgbaImage::new(canvas_w, canvas_h);
+    image::imageops::overlay(&mut canvas, &resized, pad_x.into(), pad_y.into());
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matte_with_bright_square(size: u32, square: (u32, u32, u32, u32)) -> GrayImage {
+        let mut img = GrayImage::new(size, size);
+        let (x, y, w, h) = square;
+        for sy in y..y + h {
+            for sx in x..x + w {
+                img.put_pixel(sx, sy, image::Luma([255]));
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn alpha_bbox_finds_the_thresholded_region() {
+        let matte = matte_with_bright_square(10, (2, 3, 4, 2));
+        assert_eq!(alpha_bbox(&matte, 128, 0), Some((2, 3, 4, 2)));
+    }
+
+    #[test]
+    fn alpha_bbox_expands_by_margin_and_clamps_to_bounds() {
+        let matte = matte_with_bright_square(10, (2, 3, 4, 2));
+        // Margin of 3 pushes past the left and top edges, which clamp to 0
+        // rather than go out of bounds, while the right and bottom edges
+        // (columns 2..6, rows 3..5) just grow by 3 to 9 and 8.
+        assert_eq!(alpha_bbox(&matte, 128, 3), Some((0, 0, 9, 8)));
+    }
+
+    #[test]
+    fn alpha_bbox_saturates_instead_of_overflowing_on_a_huge_margin() {
+        let matte = matte_with_bright_square(10, (2, 3, 4, 2));
+        assert_eq!(alpha_bbox(&matte, 128, u32::MAX), Some((0, 0, 10, 10)));
+    }
+
+    #[test]
+    fn alpha_bbox_returns_none_below_threshold() {
+        let matte = GrayImage::new(10, 10);
+        assert_eq!(alpha_bbox(&matte, 128, 0), None);
+    }
+
+    #[test]
+    fn crop_to_cutout_without_canvas_just_crops() {
+        let img = RgbaImage::from_pixel(10, 10, Rgba([10, 20, 30, 255]));
+        let cropped = crop_to_cutout(&img, (1, 1, 4, 3), None);
+        assert_eq!(cropped.dimensions(), (4, 3));
+    }
+
+    #[test]
+    fn crop_to_cutout_letterboxes_into_the_requested_canvas() {
+        let img = RgbaImage::from_pixel(10, 10, Rgba([10, 20, 30, 255]));
+        let canvas = crop_to_cutout(&img, (0, 0, 10, 4), Some((8, 8)));
+        assert_eq!(canvas.dimensions(), (8, 8));
+    }
+
+    #[test]
+    fn crop_to_cutout_does_not_underflow_on_an_extreme_aspect_ratio() {
+        // A very wide, very short crop rounds `new_h` up to 1 pixel, which
+        // used to be able to exceed a small canvas height and underflow the
+        // `pad_y` subtraction.
+        let img = RgbaImage::from_pixel(100, 100, Rgba([1, 2, 3, 255]));
+        let canvas = crop_to_cutout(&img, (0, 0, 100, 1), Some((4, 4)));
+        assert_eq!(canvas.dimensions(), (4, 4));
+    }
+
+    #[test]
+    fn crop_to_cutout_handles_a_zero_sized_canvas() {
+        let img = RgbaImage::from_pixel(10, 10, Rgba([1, 2, 3, 255]));
+        let canvas = crop_to_cutout(&img, (0, 0, 10, 10), Some((0, 5)));
+        assert_eq!(canvas.dimensions(), (0, 5));
+    }
+}
@@ -1,67 +1,304 @@
+use anyhow::{Result, anyhow};
 use image::{RgbImage, imageops::FilterType};
-use ndarray::Array4;
+use ndarray::{Array2, Array4, ArrayD};
+use ort::session::Session;
+use ort::value::ValueType;
 
-/// Resize while preserving aspect ratio and pad (black) to target size.
+/// Tensor layout a model's input and output use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Channel-first: input `(1, 3, H, W)`, output `(1, 1, H, W)`.
+    Nchw,
+    /// Channel-last: input `(1, H, W, 3)`, output `(1, H, W, 1)`.
+    Nhwc,
+}
+
+/// Guess the layout a session's first input expects from its declared rank
+/// and shape: a trailing channel dimension of 3 (after the batch dim) means
+/// channel-last (NHWC); anything else defaults to the crate's original
+/// channel-first (NCHW) assumption.
+pub fn detect_layout(session: &Session) -> Layout {
+    if let Some(ValueType::Tensor { shape, .. }) =
+        session.inputs.first().map(|input| &input.input_type)
+    {
+        if shape.len() == 4 && shape[3] == 3 {
+            return Layout::Nhwc;
+        }
+    }
+    Layout::Nchw
+}
+
+/// Resolve the `(width, height)` a model's first input actually expects.
+///
+/// Concrete dimensions declared on the ONNX graph take priority, so
+/// re-exported models with a different fixed resolution just work. Symbolic
+/// or dynamic axes (reported as non-positive sizes) fall back to
+/// `override_size` if the caller supplied one via `--input-size`, and finally
+/// to `default_size` (the model's historical hardcoded resolution).
+pub fn resolve_target_size(
+    session: &Session,
+    layout: Layout,
+    default_size: u32,
+    override_size: Option<u32>,
+) -> (u32, u32) {
+    if let Some(ValueType::Tensor { shape, .. }) =
+        session.inputs.first().map(|input| &input.input_type)
+    {
+        if shape.len() == 4 {
+            let (h, w) = match layout {
+                Layout::Nchw => (shape[2], shape[3]),
+                Layout::Nhwc => (shape[1], shape[2]),
+            };
+            if h > 0 && w > 0 {
+                return (w as u32, h as u32);
+            }
+        }
+    }
+
+    let size = override_size.unwrap_or(default_size);
+    (size, size)
+}
+
+/// Reduce a model's raw alpha output down to a plain `(H, W)` plane,
+/// regardless of whether it declared a 4D `(1,1,H,W)`/`(1,H,W,1)` tensor or a
+/// squeezed 3D `(1,H,W)` one (the channel dimension carries no information in
+/// that case, so layout doesn't matter for it).
+pub fn extract_alpha_plane(arr: ArrayD<f32>, layout: Layout) -> Result<Array2<f32>> {
+    match arr.ndim() {
+        4 => {
+            let a4 = arr.into_dimensionality::<ndarray::Ix4>()?;
+            let (h, w) = match layout {
+                Layout::Nchw => (a4.shape()[2], a4.shape()[3]),
+                Layout::Nhwc => (a4.shape()[1], a4.shape()[2]),
+            };
+            let mut plane = Array2::<f32>::zeros((h, w));
+            for y in 0..h {
+                for x in 0..w {
+                    plane[[y, x]] = match layout {
+                        Layout::Nchw => a4[[0, 0, y, x]],
+                        Layout::Nhwc => a4[[0, y, x, 0]],
+                    };
+                }
+            }
+            Ok(plane)
+        }
+        3 => {
+            let a3 = arr.into_dimensionality::<ndarray::Ix3>()?;
+            let (h, w) = (a3.shape()[1], a3.shape()[2]);
+            let mut plane = Array2::<f32>::zeros((h, w));
+            for y in 0..h {
+                for x in 0..w {
+                    plane[[y, x]] = a3[[0, y, x]];
+                }
+            }
+            Ok(plane)
+        }
+        d => Err(anyhow!(
+            "Unexpected output dimensionality from model: {}",
+            d
+        )),
+    }
+}
+
+/// How to fill the letterbox margins produced by [`resize_with_padding`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PaddingMode {
+    /// Solid black margins (original behavior).
+    Constant,
+    /// Clamp to the nearest boundary pixel of the resized content.
+    Edge,
+    /// Mirror the resized content into the margins without repeating the
+    /// boundary pixel (period `2*w-2` zig-zag), avoiding the hard edge a
+    /// solid-color margin creates right next to the subject.
+    Reflect,
+}
+
+/// Map a coordinate relative to resized content of width `w` into `[0, w)`
+/// using a `2*(w-1)`-period zig-zag, so offset `-k` lands on source column
+/// `k` and column `0` is never duplicated.
+fn reflect_index(c: i64, w: i64) -> i64 {
+    if w <= 1 {
+        return 0;
+    }
+    let period = 2 * (w - 1);
+    let m = c.rem_euclid(period);
+    if m >= w { period - m } else { m }
+}
+
+/// Resize while preserving aspect ratio and pad to target size using `mode`.
 /// Returns (padded_image, (pad_x, pad_y, resized_w, resized_h))
 pub fn resize_with_padding(
     img: &RgbImage,
     target_w: u32,
     target_h: u32,
+    mode: PaddingMode,
 ) -> (RgbImage, (u32, u32, u32, u32)) {
     let (orig_w, orig_h) = img.dimensions();
     let scale = f32::min(
         target_w as f32 / orig_w as f32,
         target_h as f32 / orig_h as f32,
     );
-    let new_w = (orig_w as f32 * scale).round() as u32;
-    let new_h = (orig_h as f32 * scale).round() as u32;
+    // Round-to-zero is possible for extreme aspect ratios (e.g. a 5000x1
+    // image letterboxed into 320x320), and `get_pixel` below would panic on
+    // a zero-sized `resized`, so floor both dimensions at 1 pixel.
+    let new_w = ((orig_w as f32 * scale).round() as u32).max(1);
+    let new_h = ((orig_h as f32 * scale).round() as u32).max(1);
     let resized = image::imageops::resize(img, new_w, new_h, FilterType::Lanczos3);
 
     let pad_x = (target_w - new_w) / 2;
     let pad_y = (target_h - new_h) / 2;
 
     let mut padded = RgbImage::new(target_w, target_h);
-    // fill with black
-    for (_x, _y, pixel) in padded.enumerate_pixels_mut() {
-        *pixel = image::Rgb([0, 0, 0]);
+    for ty in 0..target_h {
+        let ry = ty as i64 - pad_y as i64;
+        for tx in 0..target_w {
+            let rx = tx as i64 - pad_x as i64;
+            let in_bounds = rx >= 0 && rx < new_w as i64 && ry >= 0 && ry < new_h as i64;
+
+            let pixel = match mode {
+                PaddingMode::Constant if !in_bounds => image::Rgb([0, 0, 0]),
+                PaddingMode::Constant => *resized.get_pixel(rx as u32, ry as u32),
+                PaddingMode::Edge => {
+                    let sx = rx.clamp(0, new_w as i64 - 1) as u32;
+                    let sy = ry.clamp(0, new_h as i64 - 1) as u32;
+                    *resized.get_pixel(sx, sy)
+                }
+                PaddingMode::Reflect => {
+                    let sx = reflect_index(rx, new_w as i64) as u32;
+                    let sy = reflect_index(ry, new_h as i64) as u32;
+                    *resized.get_pixel(sx, sy)
+                }
+            };
+            padded.put_pixel(tx, ty, pixel);
+        }
     }
-    image::imageops::overlay(&mut padded, &resized, pad_x.into(), pad_y.into());
+
     (padded, (pad_x, pad_y, new_w, new_h))
 }
 
+/// Split `raw` (interleaved RGB, row-major) into `plane_len`-sized
+/// per-channel byte buffers in one contiguous pass, so the normalization
+/// step that follows can run over each channel's bytes linearly.
+fn deinterleave_rgb(raw: &[u8], plane_len: usize) -> [Vec<u8>; 3] {
+    let mut planes = [
+        vec![0u8; plane_len],
+        vec![0u8; plane_len],
+        vec![0u8; plane_len],
+    ];
+    for (i, px) in raw.chunks_exact(3).enumerate() {
+        planes[0][i] = px[0];
+        planes[1][i] = px[1];
+        planes[2][i] = px[2];
+    }
+    planes
+}
+
 /// MODNet preprocessing: resize/pad must be done before calling this.
 /// Converts an RGB image into NCHW Array4<f32> normalized to [-1, 1].
 pub fn preprocess_modnet_nchw(img: &RgbImage) -> Array4<f32> {
     let (w, h) = (img.width() as usize, img.height() as usize);
-    let mut data = Vec::with_capacity(1 * 3 * h * w);
-    for c in 0..3 {
-        for y in 0..h {
-            for x in 0..w {
-                let px = img.get_pixel(x as u32, y as u32);
+    let plane_len = h * w;
+    let planes = deinterleave_rgb(img.as_raw(), plane_len);
+
+    let mut data = vec![0f32; 3 * plane_len];
+    for (c, plane) in planes.iter().enumerate() {
+        crate::simd::normalize_channel(
+            plane,
+            &mut data[c * plane_len..(c + 1) * plane_len],
+            127.5,
+            127.5,
+        );
+    }
+    Array4::from_shape_vec((1, 3, h, w), data).expect("shape must match")
+}
+
+/// MODNet preprocessing, channel-last variant for NHWC-exported models.
+/// Converts an RGB image into `(1, H, W, 3)` Array4<f32> normalized to [-1, 1].
+pub fn preprocess_modnet_nhwc(img: &RgbImage) -> Array4<f32> {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let mut data = Vec::with_capacity(h * w * 3);
+    for y in 0..h {
+        for x in 0..w {
+            let px = img.get_pixel(x as u32, y as u32);
+            for c in 0..3 {
                 let v = px[c] as f32;
                 data.push((v - 127.5) / 127.5_f32);
             }
         }
     }
-    Array4::from_shape_vec((1, 3, h, w), data).expect("shape must match")
+    Array4::from_shape_vec((1, h, w, 3), data).expect("shape must match")
 }
 
 /// U²-Net preprocessing: ImageNet mean/std normalization, expects inputs scaled [0,1].
 /// Input should already be resized/padded to target (320).
 pub fn preprocess_u2net_nchw(img: &RgbImage) -> Array4<f32> {
+    // `(v/255 - mean) / std == (v - mean*255) / (std*255)`, so the SIMD
+    // normalizer can still run directly over the raw `u8` channel bytes.
+    let mean = [0.485_f32, 0.456_f32, 0.406_f32];
+    let std = [0.229_f32, 0.224_f32, 0.225_f32];
+
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let plane_len = h * w;
+    let planes = deinterleave_rgb(img.as_raw(), plane_len);
+
+    let mut data = vec![0f32; 3 * plane_len];
+    for (c, plane) in planes.iter().enumerate() {
+        crate::simd::normalize_channel(
+            plane,
+            &mut data[c * plane_len..(c + 1) * plane_len],
+            mean[c] * 255.0,
+            std[c] * 255.0,
+        );
+    }
+    Array4::from_shape_vec((1, 3, h, w), data).expect("shape must match")
+}
+
+/// U²-Net preprocessing, channel-last variant for NHWC-exported models.
+/// Converts an RGB image into `(1, H, W, 3)` Array4<f32>, ImageNet-normalized.
+pub fn preprocess_u2net_nhwc(img: &RgbImage) -> Array4<f32> {
     let mean = [0.485_f32, 0.456_f32, 0.406_f32];
     let std = [0.229_f32, 0.224_f32, 0.225_f32];
 
     let (w, h) = (img.width() as usize, img.height() as usize);
-    let mut data = Vec::with_capacity(1 * 3 * h * w);
-    for c in 0..3 {
-        for y in 0..h {
-            for x in 0..w {
-                let px = img.get_pixel(x as u32, y as u32);
+    let mut data = Vec::with_capacity(h * w * 3);
+    for y in 0..h {
+        for x in 0..w {
+            let px = img.get_pixel(x as u32, y as u32);
+            for c in 0..3 {
                 let v = (px[c] as f32) / 255.0_f32;
                 data.push((v - mean[c]) / std[c]);
             }
         }
     }
-    Array4::from_shape_vec((1, 3, h, w), data).expect("shape must match")
+    Array4::from_shape_vec((1, h, w, 3), data).expect("shape must match")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflect_index_zig_zags_without_duplicating_the_boundary() {
+        // w = 4, period = 2*(4-1) = 6: 0,1,2,3,2,1 | 0,1,2,3,2,1 | ...
+        let w = 4;
+        let expected = [0, 1, 2, 3, 2, 1];
+        for (offset, &want) in expected.iter().enumerate() {
+            assert_eq!(reflect_index(offset as i64, w), want, "offset {offset}");
+        }
+
+        // Negative offsets mirror into the content: -1 lands on column 1, not 0.
+        assert_eq!(reflect_index(-1, w), 1);
+        assert_eq!(reflect_index(-2, w), 2);
+
+        // A full period back around should land on the same column.
+        assert_eq!(reflect_index(6, w), reflect_index(0, w));
+    }
+
+    #[test]
+    fn reflect_index_handles_degenerate_widths() {
+        assert_eq!(reflect_index(0, 0), 0);
+        assert_eq!(reflect_index(5, 0), 0);
+        assert_eq!(reflect_index(0, 1), 0);
+        assert_eq!(reflect_index(5, 1), 0);
+    }
 }
@@ -0,0 +1,78 @@
+use anyhow::Result;
+use image::{GrayImage, Luma, RgbImage, imageops::FilterType};
+use ndarray::{Array2, Array4, ArrayD};
+
+use crate::preprocess::{Layout, PaddingMode, extract_alpha_plane, resize_with_padding};
+
+/// Model-specific knowledge needed to run a matting model on any backend:
+/// its input resolution and tensor layout, and how to turn an image into an
+/// input tensor and a raw output tensor back into an alpha plane.
+///
+/// `run_modnet`/`run_u2net` used to each hardcode this flow; implementing
+/// this trait is now the model-specific part of adding one, while the
+/// shared flow lives in [`segment`]. A new model still needs its own
+/// `ModelKind` variant wherever the batch and serve modes match on it.
+pub trait Segmenter {
+    /// The `(width, height)` this model's input tensor expects.
+    fn target_size(&self) -> (u32, u32);
+
+    /// Tensor layout this model's input and output use.
+    fn layout(&self) -> Layout;
+
+    /// Normalize an already letterboxed image into the model's input tensor.
+    fn preprocess(&self, img: &RgbImage) -> Array4<f32>;
+
+    /// Reduce the model's raw output tensor down to an `(H, W)` alpha plane
+    /// in `[0, 1]`. The default just defers to [`extract_alpha_plane`], which
+    /// covers every model in this crate so far.
+    fn postprocess(&self, output: ArrayD<f32>) -> Result<Array2<f32>> {
+        extract_alpha_plane(output, self.layout())
+    }
+}
+
+/// Runs a [`Segmenter`]'s input tensor through a concrete inference runtime
+/// and returns its raw output tensor. Implemented once per backend (`ort`,
+/// `tract`, ...) in [`crate::backend`], independent of which model is run.
+pub trait Inferer {
+    fn infer(&mut self, input: Array4<f32>) -> Result<ArrayD<f32>>;
+}
+
+/// Shared end-to-end flow for any [`Segmenter`] on any [`Inferer`] backend:
+/// letterbox to the model's input size, preprocess, run inference,
+/// postprocess, crop out the padding, and resize the alpha plane back to the
+/// source image's dimensions.
+pub fn segment(
+    segmenter: &dyn Segmenter,
+    inferer: &mut dyn Inferer,
+    img: &RgbImage,
+    padding_mode: PaddingMode,
+) -> Result<GrayImage> {
+    let (target_w, target_h) = segmenter.target_size();
+    let (padded_img, (pad_x, pad_y, resized_w, resized_h)) =
+        resize_with_padding(img, target_w, target_h, padding_mode);
+
+    let input_arr = segmenter.preprocess(&padded_img);
+    let output = inferer.infer(input_arr)?;
+    let alpha_plane = segmenter.postprocess(output)?;
+
+    let (mh, mw) = alpha_plane.dim();
+    let mut matte_full = GrayImage::new(mw as u32, mh as u32);
+    for y in 0..mh {
+        for x in 0..mw {
+            let val = alpha_plane[[y, x]].clamp(0.0, 1.0);
+            let byte = (val * 255.0).round() as u8;
+            matte_full.put_pixel(x as u32, y as u32, Luma([byte]));
+        }
+    }
+
+    let matte_cropped =
+        image::imageops::crop_imm(&matte_full, pad_x.into(), pad_y.into(), resized_w, resized_h)
+            .to_image();
+
+    Ok(image::imageops::resize(
+        &matte_cropped,
+        img.width(),
+        img.height(),
+        FilterType::Lanczos3,
+    ))
+}
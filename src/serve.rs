@@ -0,0 +1,119 @@
+use std::io::Read;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::backend::{Backend, ModelBackend};
+use crate::batch::ModelKind;
+use crate::modnet::resolve_modnet_segmenter;
+use crate::postprocess::{OutputOptions, encode_output};
+use crate::preprocess::{Layout, PaddingMode};
+use crate::segmenter::{Segmenter, segment};
+use crate::u2net::resolve_u2net_segmenter;
+
+/// The model backend and the segmenter resolved for it, built once and
+/// reused for every request.
+struct ServeState {
+    model_backend: ModelBackend,
+    segmenter: Box<dyn Segmenter>,
+}
+
+/// Run a blocking HTTP server that keeps the model `Session` (and its
+/// execution provider warm-up) alive across requests instead of paying
+/// model-load cost per call.
+///
+/// `POST /segment` with an image body in the request returns the matte (or
+/// RGBA/cutout PNG, per `output_opts`), reusing the same
+/// [`crate::segmenter::segment`] pipeline the one-shot and batch modes use.
+/// The model backend is shared behind a single mutex, so concurrent
+/// requests queue and run one at a time rather than racing on it — most
+/// backends (in particular `ort`'s GPU execution providers) aren't safe to
+/// drive from multiple threads at once.
+#[allow(clippy::too_many_arguments)]
+pub fn run_serve(
+    model: ModelKind,
+    model_path: &str,
+    addr: &str,
+    threads: usize,
+    use_cuda: bool,
+    use_tensorrt: bool,
+    use_directml: bool,
+    device_id: i32,
+    input_size: Option<u32>,
+    layout: Option<Layout>,
+    padding_mode: PaddingMode,
+    backend: Backend,
+    output_opts: OutputOptions,
+) -> Result<()> {
+    let mut model_backend = ModelBackend::build(
+        backend,
+        model_path,
+        threads,
+        use_cuda,
+        use_tensorrt,
+        use_directml,
+        device_id,
+    )?;
+    let segmenter: Box<dyn Segmenter> = match model {
+        ModelKind::Modnet => Box::new(resolve_modnet_segmenter(&mut model_backend, layout, input_size)),
+        ModelKind::U2net => Box::new(resolve_u2net_segmenter(&mut model_backend, layout, input_size)),
+    };
+    let state = Mutex::new(ServeState {
+        model_backend,
+        segmenter,
+    });
+
+    let server = tiny_http::Server::http(addr)
+        .map_err(|err| anyhow!("Failed to bind HTTP server on {addr}: {err}"))?;
+    println!("Serving on http://{addr} (POST an image to /segment)");
+
+    std::thread::scope(|scope| {
+        for request in server.incoming_requests() {
+            let state = &state;
+            let output_opts = &output_opts;
+            scope.spawn(move || {
+                if let Err(err) = handle_request(request, state, output_opts, padding_mode) {
+                    eprintln!("Request failed: {err:#}");
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_request(
+    mut request: tiny_http::Request,
+    state: &Mutex<ServeState>,
+    output_opts: &OutputOptions,
+    padding_mode: PaddingMode,
+) -> Result<()> {
+    if request.url() != "/segment" {
+        request.respond(tiny_http::Response::from_string("Not found").with_status_code(404))?;
+        return Ok(());
+    }
+
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .read_to_end(&mut body)
+        .context("Failed to read request body")?;
+    let img = image::load_from_memory(&body)
+        .context("Failed to decode uploaded image")?
+        .to_rgb8();
+
+    let png = {
+        let mut state = state.lock().unwrap();
+        let ServeState {
+            model_backend,
+            segmenter,
+        } = &mut *state;
+        let matte = segment(segmenter.as_ref(), model_backend, &img, padding_mode)?;
+        encode_output(&img, matte, output_opts)?
+    };
+
+    let content_type = tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"image/png"[..])
+        .expect("static header is valid");
+    request.respond(tiny_http::Response::from_data(png).with_header(content_type))?;
+    Ok(())
+}
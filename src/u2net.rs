@@ -1,92 +1,98 @@
-use anyhow::{Result, anyhow};
-use image::{GrayImage, ImageReader, Luma, imageops::FilterType};
-use ndarray::{Ix3, Ix4};
-use ort::session::{Session, builder::GraphOptimizationLevel};
-use ort::value::Tensor;
+use anyhow::Result;
+use image::{ImageReader, RgbImage};
+use ndarray::Array4;
 
-use crate::preprocess::{preprocess_u2net_nchw, resize_with_padding};
+use crate::backend::{Backend, ModelBackend};
+use crate::preprocess::{Layout, PaddingMode, preprocess_u2net_nchw, preprocess_u2net_nhwc};
+use crate::segmenter::{Segmenter, segment};
 
-/// Run U²-Net (positional input) — typical target size: 320
-pub fn run_u2net(model_path: &str, input_path: &str, output_path: &str) -> Result<()> {
-    let mut session = Session::builder()?
-        .with_optimization_level(GraphOptimizationLevel::Level3)?
-        .commit_from_file(model_path)?;
+/// U²-Net's historical fixed export resolution, used when the loaded graph
+/// declares a dynamic input shape and the caller didn't override it.
+pub(crate) const DEFAULT_TARGET_SIZE: u32 = 320;
 
-    // println!(
-    //     "Model inputs: {:?}",
-    //     session.inputs.iter().map(|i| &i.name).collect::<Vec<_>>()
-    // );
-    // println!(
-    //     "Model outputs: {:?}",
-    //     session.outputs.iter().map(|o| &o.name).collect::<Vec<_>>()
-    // );
+/// [`Segmenter`] for U²-Net: channel-first or channel-last input with
+/// ImageNet mean/std normalization, single-channel alpha output.
+pub struct U2netSegmenter {
+    pub target_size: (u32, u32),
+    pub layout: Layout,
+}
 
-    let img = ImageReader::open(input_path)?.decode()?.to_rgb8();
-    // U^2-Net commonly uses 320 (authors / many exports use 320x320)
-    let (padded_img, (pad_x, pad_y, resized_w, resized_h)) = resize_with_padding(&img, 320, 320);
-    let input_arr = preprocess_u2net_nchw(&padded_img);
-    let input_tensor = Tensor::from_array(input_arr)?;
+impl Segmenter for U2netSegmenter {
+    fn target_size(&self) -> (u32, u32) {
+        self.target_size
+    }
 
-    let outputs = session.run(ort::inputs![input_tensor])?;
-    let arr_view = outputs[0].try_extract_array::<f32>()?;
-    let arr_owned = arr_view.to_owned();
+    fn layout(&self) -> Layout {
+        self.layout
+    }
 
-    // Support either 4D (1,1,H,W) or 3D (1,H,W)
-    let alpha4 = match arr_owned.ndim() {
-        4 => arr_owned.into_dimensionality::<Ix4>()?,
-        3 => {
-            let a3 = arr_owned.into_dimensionality::<Ix3>()?;
-            let (b, h, w) = (a3.shape()[0], a3.shape()[1], a3.shape()[2]);
-            let mut out = ndarray::Array4::<f32>::zeros((b, 1, h, w));
-            for bi in 0..b {
-                for y in 0..h {
-                    for x in 0..w {
-                        out[[bi, 0, y, x]] = a3[[bi, y, x]];
-                    }
-                }
-            }
-            out
+    fn preprocess(&self, img: &RgbImage) -> Array4<f32> {
+        match self.layout {
+            Layout::Nchw => preprocess_u2net_nchw(img),
+            Layout::Nhwc => preprocess_u2net_nhwc(img),
         }
-        d => {
-            return Err(anyhow!(
-                "Unexpected output dimensionality from model: {}",
-                d
-            ));
-        }
-    };
+    }
+}
 
-    // Build grayscale matte at model resolution (likely 320x320)
-    let mh = alpha4.shape()[2];
-    let mw = alpha4.shape()[3];
-    let mut matte_full = GrayImage::new(mw as u32, mh as u32);
-    for y in 0..mh {
-        for x in 0..mw {
-            let val = alpha4[[0, 0, y, x]].clamp(0.0, 1.0);
-            let byte = (val * 255.0).round() as u8;
-            matte_full.put_pixel(x as u32, y as u32, Luma([byte]));
+/// Resolve the layout and target size U²-Net should run at, introspecting
+/// `backend`'s `ort::Session` when one is available.
+pub(crate) fn resolve_u2net_segmenter(
+    backend: &mut ModelBackend,
+    layout: Option<Layout>,
+    input_size: Option<u32>,
+) -> U2netSegmenter {
+    let layout = layout.unwrap_or_else(|| {
+        backend
+            .session()
+            .map(crate::preprocess::detect_layout)
+            .unwrap_or(Layout::Nchw)
+    });
+    let target_size = match backend.session() {
+        Some(session) => {
+            crate::preprocess::resolve_target_size(session, layout, DEFAULT_TARGET_SIZE, input_size)
         }
-    }
+        None => {
+            let size = input_size.unwrap_or(DEFAULT_TARGET_SIZE);
+            (size, size)
+        }
+    };
+    U2netSegmenter { target_size, layout }
+}
 
-    // Crop out padding (padded to 320x320)
-    let matte_cropped = image::imageops::crop_imm(
-        &matte_full,
-        pad_x.into(),
-        pad_y.into(),
-        resized_w,
-        resized_h,
-    )
-    .to_image();
+/// Run U²-Net end-to-end on a single input file, writing the result per
+/// `output_opts`.
+#[allow(clippy::too_many_arguments)]
+pub fn run_u2net(
+    model_path: &str,
+    input_path: &str,
+    output_path: &str,
+    threads: usize,
+    use_cuda: bool,
+    use_tensorrt: bool,
+    use_directml: bool,
+    device_id: i32,
+    input_size: Option<u32>,
+    layout: Option<Layout>,
+    padding_mode: PaddingMode,
+    backend: Backend,
+    output_opts: &crate::postprocess::OutputOptions,
+) -> Result<()> {
+    let mut model_backend = ModelBackend::build(
+        backend,
+        model_path,
+        threads,
+        use_cuda,
+        use_tensorrt,
+        use_directml,
+        device_id,
+    )?;
+    let segmenter = resolve_u2net_segmenter(&mut model_backend, layout, input_size);
 
-    // Resize back to original input size
-    let matte_final = image::imageops::resize(
-        &matte_cropped,
-        img.width(),
-        img.height(),
-        FilterType::Lanczos3,
-    );
+    let img = ImageReader::open(input_path)?.decode()?.to_rgb8();
+    let matte = segment(&segmenter, &mut model_backend, &img, padding_mode)?;
 
-    matte_final.save(output_path)?;
-    println!("Saved U2Net alpha to {}", output_path);
+    crate::postprocess::write_output(&img, matte, std::path::Path::new(output_path), output_opts)?;
+    println!("Saved U2Net output to {}", output_path);
 
     Ok(())
 }